@@ -1,23 +1,134 @@
-use embedded_hal::{digital::*, spi};
+use embedded_hal::{
+    delay::DelayNs,
+    digital::*,
+    spi::{self, Mode, Phase, Polarity},
+};
 
-pub struct SoftSpi<In: InputPin, Out: OutputPin> {
+use crate::BitOrder;
+
+pub struct SoftSpi<In: InputPin, Out: OutputPin, Delay: DelayNs> {
     sck: Out,
     miso: In,
     mosi: Out,
+    mode: Mode,
+    bit_order: BitOrder,
+    delay: Delay,
+    half_period_ns: u32,
 }
 
-impl<In: InputPin, Out: OutputPin> SoftSpi<In, Out> {
-    pub fn new(sck: Out, miso: In, mosi: Out) -> SoftSpi<In, Out> {
-        let mut this = SoftSpi { sck, miso, mosi };
-        let _ = this.sck.set_high();
+impl<In: InputPin, Out: OutputPin, Delay: DelayNs> SoftSpi<In, Out, Delay> {
+    /// Create a bus that clocks SCK with a `half_period_ns` nanosecond delay
+    /// after every edge, yielding a clock period of `2 * half_period_ns`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sck: Out,
+        miso: In,
+        mosi: Out,
+        mode: Mode,
+        bit_order: BitOrder,
+        delay: Delay,
+        half_period_ns: u32,
+    ) -> SoftSpi<In, Out, Delay> {
+        let mut this = SoftSpi {
+            sck,
+            miso,
+            mosi,
+            mode,
+            bit_order,
+            delay,
+            half_period_ns,
+        };
+        this.sck_idle();
         this
     }
+
+    /// Create a bus targeting an approximate `frequency_hz` SCK frequency.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_frequency(
+        sck: Out,
+        miso: In,
+        mosi: Out,
+        mode: Mode,
+        bit_order: BitOrder,
+        delay: Delay,
+        frequency_hz: u32,
+    ) -> SoftSpi<In, Out, Delay> {
+        let half_period_ns = crate::half_period_ns_from_frequency(frequency_hz);
+        Self::new(sck, miso, mosi, mode, bit_order, delay, half_period_ns)
+    }
+
+    /// Extract bit number `bits` (0 = first bit shifted out) from `word`
+    /// according to the configured [`BitOrder`].
+    fn bit_out(&self, word: u8, bits: u8) -> bool {
+        match self.bit_order {
+            BitOrder::MsbFirst => (word << bits) & 0x80 != 0,
+            BitOrder::LsbFirst => (word >> bits) & 1 != 0,
+        }
+    }
+
+    /// Fold a sampled bit into `byte` at position `bits` according to the
+    /// configured [`BitOrder`].
+    fn bit_in(&self, byte: &mut u8, bits: u8, sampled: bool) {
+        match self.bit_order {
+            BitOrder::MsbFirst => {
+                *byte <<= 1;
+                if sampled {
+                    *byte += 1;
+                }
+            }
+            BitOrder::LsbFirst => {
+                if sampled {
+                    *byte |= 1 << bits;
+                }
+            }
+        }
+    }
+
+    /// Drive SCK to the idle level dictated by [`Polarity`], then wait out the half period.
+    fn sck_idle(&mut self) {
+        match self.mode.polarity {
+            Polarity::IdleLow => self.sck.set_low().ok(),
+            Polarity::IdleHigh => self.sck.set_high().ok(),
+        };
+        self.delay.delay_ns(self.half_period_ns);
+    }
+
+    /// Drive SCK to the active (non-idle) level dictated by [`Polarity`], then wait out the half period.
+    fn sck_active(&mut self) {
+        match self.mode.polarity {
+            Polarity::IdleLow => self.sck.set_high().ok(),
+            Polarity::IdleHigh => self.sck.set_low().ok(),
+        };
+        self.delay.delay_ns(self.half_period_ns);
+    }
+
+    /// Clock one bit in and/or out, honoring [`Phase`].
+    ///
+    /// `mosi_bit` is driven unconditionally; pass the idle-data value (e.g.
+    /// `false`) when only reading. The sampled MISO level is returned.
+    fn clock_bit(&mut self, mosi_bit: bool) -> bool {
+        match self.mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                self.mosi.set_state(mosi_bit.into()).ok();
+                self.sck_active();
+                let sampled = Some(true) == self.miso.is_high().ok();
+                self.sck_idle();
+                sampled
+            }
+            Phase::CaptureOnSecondTransition => {
+                self.sck_active();
+                self.mosi.set_state(mosi_bit.into()).ok();
+                self.sck_idle();
+                Some(true) == self.miso.is_high().ok()
+            }
+        }
+    }
 }
-impl<In: InputPin, Out: OutputPin> spi::ErrorType for SoftSpi<In, Out> {
+impl<In: InputPin, Out: OutputPin, Delay: DelayNs> spi::ErrorType for SoftSpi<In, Out, Delay> {
     type Error = spi::ErrorKind;
 }
 
-impl<In: InputPin, Out: OutputPin> spi::SpiBus for SoftSpi<In, Out> {
+impl<In: InputPin, Out: OutputPin, Delay: DelayNs> spi::SpiBus for SoftSpi<In, Out, Delay> {
     /// Read `words` from the slave.
     ///
     /// The word value sent on MOSI during reading is implementation-defined,
@@ -27,16 +138,12 @@ impl<In: InputPin, Out: OutputPin> spi::SpiBus for SoftSpi<In, Out> {
     /// complete. See the [module-level documentation](self) for details.
     fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
         for bytes in 0..words.len() {
-            for _ in 0..8 {
-                // Clock high then low
-                self.sck.set_low().ok();
-                self.sck.set_high().ok();
-                // Read the MISO pin after clocking the data in
-                words[bytes] <<= 1;
-                if Some(true) == self.miso.is_high().ok() {
-                    words[bytes] += 1;
-                }
+            let mut byte = 0_u8;
+            for bits in 0..8 {
+                let sampled = self.clock_bit(false);
+                self.bit_in(&mut byte, bits, sampled);
             }
+            words[bytes] = byte;
         }
         Ok(())
     }
@@ -48,11 +155,8 @@ impl<In: InputPin, Out: OutputPin> spi::SpiBus for SoftSpi<In, Out> {
     fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
         for bytes in 0..words.len() {
             for bits in 0..8 {
-                let bit = (words[bytes] << bits) & 0x80;
-                self.mosi.set_state((bit != 0).into()).ok(); // Set the MOSI pin to the current bit value
-                                                             // Clock high then low
-                self.sck.set_low().ok();
-                self.sck.set_high().ok();
+                let bit = self.bit_out(words[bytes], bits);
+                self.clock_bit(bit);
             }
         }
         Ok(())
@@ -77,19 +181,13 @@ impl<In: InputPin, Out: OutputPin> spi::SpiBus for SoftSpi<In, Out> {
         );
 
         for bytes in 0..read.len() {
+            let mut byte = 0_u8;
             for bits in 0..8 {
-                let bit = (write[bytes] << bits) & 0x80;
-                self.mosi.set_state((bit != 0).into()).ok(); // Set the MOSI pin to the current bit value
-                                                             // Clock high then low
-                self.sck.set_low().ok();
-                self.sck.set_high().ok();
-
-                // Read the MISO pin after clocking the data in
-                read[bytes] <<= 1;
-                if Some(true) == self.miso.is_high().ok() {
-                    read[bytes] = read[bytes] + 1;
-                }
+                let out_bit = self.bit_out(write[bytes], bits);
+                let sampled = self.clock_bit(out_bit);
+                self.bit_in(&mut byte, bits, sampled);
             }
+            read[bytes] = byte;
         }
         Ok(())
     }
@@ -102,21 +200,11 @@ impl<In: InputPin, Out: OutputPin> spi::SpiBus for SoftSpi<In, Out> {
     /// complete. See the [module-level documentation](self) for details.
     fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
         for bytes in 0..words.len() {
-            let mut read_byte = 0 as u8;
+            let mut read_byte = 0_u8;
             for bits in 0..8 {
-                let bit = (words[bytes] << bits) & 0x80;
-                self.mosi.set_high().ok();
-                self.mosi.set_state((bit != 0).into()).ok(); // Set the MOSI pin to the current bit value
-
-                // Clock high then low
-                self.sck.set_low().ok();
-                self.sck.set_high().ok();
-
-                // Read the MISO pin after clocking the data in
-                read_byte <<= 1;
-                if Some(true) == self.miso.is_high().ok() {
-                    read_byte += 1;
-                }
+                let out_bit = self.bit_out(words[bytes], bits);
+                let sampled = self.clock_bit(out_bit);
+                self.bit_in(&mut read_byte, bits, sampled);
             }
             words[bytes] = read_byte;
         }
@@ -130,3 +218,202 @@ impl<In: InputPin, Out: OutputPin> spi::SpiBus for SoftSpi<In, Out> {
         Ok(())
     }
 }
+
+#[cfg(feature = "async")]
+impl<In: InputPin, Out: OutputPin, Delay> SoftSpi<In, Out, Delay>
+where
+    Delay: DelayNs + embedded_hal_async::delay::DelayNs,
+{
+    /// Async counterpart of [`Self::sck_idle`], yielding to the executor for the half period.
+    async fn sck_idle_async(&mut self) {
+        match self.mode.polarity {
+            Polarity::IdleLow => self.sck.set_low().ok(),
+            Polarity::IdleHigh => self.sck.set_high().ok(),
+        };
+        embedded_hal_async::delay::DelayNs::delay_ns(&mut self.delay, self.half_period_ns).await;
+    }
+
+    /// Async counterpart of [`Self::sck_active`], yielding to the executor for the half period.
+    async fn sck_active_async(&mut self) {
+        match self.mode.polarity {
+            Polarity::IdleLow => self.sck.set_high().ok(),
+            Polarity::IdleHigh => self.sck.set_low().ok(),
+        };
+        embedded_hal_async::delay::DelayNs::delay_ns(&mut self.delay, self.half_period_ns).await;
+    }
+
+    /// Async counterpart of [`Self::clock_bit`].
+    async fn clock_bit_async(&mut self, mosi_bit: bool) -> bool {
+        match self.mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                self.mosi.set_state(mosi_bit.into()).ok();
+                self.sck_active_async().await;
+                let sampled = Some(true) == self.miso.is_high().ok();
+                self.sck_idle_async().await;
+                sampled
+            }
+            Phase::CaptureOnSecondTransition => {
+                self.sck_active_async().await;
+                self.mosi.set_state(mosi_bit.into()).ok();
+                self.sck_idle_async().await;
+                Some(true) == self.miso.is_high().ok()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<In: InputPin, Out: OutputPin, Delay> embedded_hal_async::spi::SpiBus
+    for SoftSpi<In, Out, Delay>
+where
+    Delay: DelayNs + embedded_hal_async::delay::DelayNs,
+{
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for bytes in 0..words.len() {
+            let mut byte = 0_u8;
+            for bits in 0..8 {
+                let sampled = self.clock_bit_async(false).await;
+                self.bit_in(&mut byte, bits, sampled);
+            }
+            words[bytes] = byte;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        for bytes in 0..words.len() {
+            for bits in 0..8 {
+                let bit = self.bit_out(words[bytes], bits);
+                self.clock_bit_async(bit).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        assert_eq!(
+            read.len(),
+            write.len(),
+            "Read and write buffers must be the same length"
+        );
+
+        for bytes in 0..read.len() {
+            let mut byte = 0_u8;
+            for bits in 0..8 {
+                let out_bit = self.bit_out(write[bytes], bits);
+                let sampled = self.clock_bit_async(out_bit).await;
+                self.bit_in(&mut byte, bits, sampled);
+            }
+            read[bytes] = byte;
+        }
+        Ok(())
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for bytes in 0..words.len() {
+            let mut read_byte = 0_u8;
+            for bits in 0..8 {
+                let out_bit = self.bit_out(words[bytes], bits);
+                let sampled = self.clock_bit_async(out_bit).await;
+                self.bit_in(&mut read_byte, bits, sampled);
+            }
+            words[bytes] = read_byte;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use embedded_hal::spi::SpiBus;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State as PinState, Transaction as PinTransaction};
+    use std::vec::Vec;
+
+    fn sck_transactions(bits: usize) -> Vec<PinTransaction> {
+        // `new` first drives SCK to its idle (low) level.
+        core::iter::once(PinTransaction::set(PinState::Low))
+            .chain((0..bits).flat_map(|_| {
+                [PinTransaction::set(PinState::High), PinTransaction::set(PinState::Low)]
+            }))
+            .collect()
+    }
+
+    fn bit_at(byte: u8, bit_order: BitOrder, bits: u8) -> bool {
+        match bit_order {
+            BitOrder::MsbFirst => (byte << bits) & 0x80 != 0,
+            BitOrder::LsbFirst => (byte >> bits) & 1 != 0,
+        }
+    }
+
+    /// Run a single-byte `transfer` and return the byte assembled from `sampled_bits`
+    /// (one entry per clocked bit, in clocking order) driven back on miso.
+    fn run_transfer(phase: Phase, bit_order: BitOrder, write: u8, sampled_bits: &[bool; 8]) -> u8 {
+        let sck = PinMock::new(&sck_transactions(8));
+
+        let mosi_transactions: Vec<_> = (0..8)
+            .map(|b| {
+                PinTransaction::set(if bit_at(write, bit_order, b) {
+                    PinState::High
+                } else {
+                    PinState::Low
+                })
+            })
+            .collect();
+        let mosi = PinMock::new(&mosi_transactions);
+
+        let miso_transactions: Vec<_> = sampled_bits
+            .iter()
+            .map(|&bit| PinTransaction::get(if bit { PinState::High } else { PinState::Low }))
+            .collect();
+        let miso = PinMock::new(&miso_transactions);
+
+        let mode = Mode {
+            polarity: Polarity::IdleLow,
+            phase,
+        };
+        let mut spi = SoftSpi::new(sck, miso, mosi, mode, bit_order, NoopDelay::new(), 0);
+
+        let mut read = [0u8];
+        spi.transfer(&mut read, &[write]).unwrap();
+
+        spi.sck.done();
+        spi.mosi.done();
+        spi.miso.done();
+        read[0]
+    }
+
+    #[test]
+    fn transfer_first_transition_msb_first() {
+        let sampled = [true, false, true, false, true, false, true, false];
+        let read = run_transfer(Phase::CaptureOnFirstTransition, BitOrder::MsbFirst, 0xB2, &sampled);
+        assert_eq!(read, 0xAA);
+    }
+
+    #[test]
+    fn transfer_first_transition_lsb_first() {
+        let sampled = [true, false, true, false, true, false, true, false];
+        let read = run_transfer(Phase::CaptureOnFirstTransition, BitOrder::LsbFirst, 0xB2, &sampled);
+        assert_eq!(read, 0x55);
+    }
+
+    #[test]
+    fn transfer_second_transition_msb_first() {
+        let sampled = [true, false, true, false, true, false, true, false];
+        let read = run_transfer(Phase::CaptureOnSecondTransition, BitOrder::MsbFirst, 0xB2, &sampled);
+        assert_eq!(read, 0xAA);
+    }
+
+    #[test]
+    fn transfer_second_transition_lsb_first() {
+        let sampled = [true, false, true, false, true, false, true, false];
+        let read = run_transfer(Phase::CaptureOnSecondTransition, BitOrder::LsbFirst, 0xB2, &sampled);
+        assert_eq!(read, 0x55);
+    }
+}
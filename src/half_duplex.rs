@@ -1,38 +1,208 @@
-use embedded_hal::{digital::*, spi};
+use embedded_hal::{
+    delay::DelayNs,
+    digital::*,
+    spi::{self, Mode, Phase, Polarity},
+};
 
-pub struct SoftSpi<Out, InOut>
+use crate::BitOrder;
+
+pub struct SoftSpi<Out, InOut, Delay>
 where
     Out: OutputPin,
     InOut: InputPin + OutputPin,
+    Delay: DelayNs,
 {
     sck: Out,
     sda: InOut,
+    mode: Mode,
+    bit_order: BitOrder,
+    delay: Delay,
+    half_period_ns: u32,
+    fill_byte: u8,
 }
 
-impl<Out, InOut> SoftSpi<Out, InOut>
+impl<Out, InOut, Delay> SoftSpi<Out, InOut, Delay>
 where
     Out: OutputPin,
     InOut: InputPin + OutputPin,
+    Delay: DelayNs,
 {
     /// **sda needs to be pullup**
-    pub fn new(sck: Out, sda: InOut) -> Self {
-        let mut this = SoftSpi { sck, sda };
-        let _ = this.sck.set_high();
+    ///
+    /// Clocks SCK with a `half_period_ns` nanosecond delay after every edge,
+    /// yielding a clock period of `2 * half_period_ns` for `read` and `write`.
+    /// `transfer` and `transfer_in_place` add one more half period per bit
+    /// while sda is turned around, so each of their bits takes `3 * half_period_ns`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sck: Out,
+        sda: InOut,
+        mode: Mode,
+        bit_order: BitOrder,
+        delay: Delay,
+        half_period_ns: u32,
+    ) -> Self {
+        let mut this = SoftSpi {
+            sck,
+            sda,
+            mode,
+            bit_order,
+            delay,
+            half_period_ns,
+            fill_byte: 0x00,
+        };
+        this.sck_idle();
         this
     }
+
+    /// Set the word driven on sda once `write` is exhausted during a
+    /// [`transfer`](spi::SpiBus::transfer). Defaults to `0x00`.
+    pub fn set_fill_byte(&mut self, fill_byte: u8) {
+        self.fill_byte = fill_byte;
+    }
+
+    /// Create a bus targeting an approximate `frequency_hz` SCK frequency.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_frequency(
+        sck: Out,
+        sda: InOut,
+        mode: Mode,
+        bit_order: BitOrder,
+        delay: Delay,
+        frequency_hz: u32,
+    ) -> Self {
+        let half_period_ns = crate::half_period_ns_from_frequency(frequency_hz);
+        Self::new(sck, sda, mode, bit_order, delay, half_period_ns)
+    }
+
+    /// Extract bit number `bits` (0 = first bit shifted out) from `word`
+    /// according to the configured [`BitOrder`].
+    fn bit_out(&self, word: u8, bits: u8) -> bool {
+        match self.bit_order {
+            BitOrder::MsbFirst => (word << bits) & 0x80 != 0,
+            BitOrder::LsbFirst => (word >> bits) & 1 != 0,
+        }
+    }
+
+    /// Fold a sampled bit into `byte` at position `bits` according to the
+    /// configured [`BitOrder`].
+    fn bit_in(&self, byte: &mut u8, bits: u8, sampled: bool) {
+        match self.bit_order {
+            BitOrder::MsbFirst => {
+                *byte <<= 1;
+                if sampled {
+                    *byte += 1;
+                }
+            }
+            BitOrder::LsbFirst => {
+                if sampled {
+                    *byte |= 1 << bits;
+                }
+            }
+        }
+    }
+
+    /// Drive SCK to the idle level dictated by [`Polarity`], then wait out the half period.
+    fn sck_idle(&mut self) {
+        match self.mode.polarity {
+            Polarity::IdleLow => self.sck.set_low().ok(),
+            Polarity::IdleHigh => self.sck.set_high().ok(),
+        };
+        self.delay.delay_ns(self.half_period_ns);
+    }
+
+    /// Drive SCK to the active (non-idle) level dictated by [`Polarity`], then wait out the half period.
+    fn sck_active(&mut self) {
+        match self.mode.polarity {
+            Polarity::IdleLow => self.sck.set_high().ok(),
+            Polarity::IdleHigh => self.sck.set_low().ok(),
+        };
+        self.delay.delay_ns(self.half_period_ns);
+    }
+
+    /// Clock one edge pair while sampling sda, honoring [`Phase`].
+    fn clock_sample(&mut self) -> bool {
+        match self.mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                self.sck_active();
+                let sampled = Some(true) == self.sda.is_high().ok();
+                self.sck_idle();
+                sampled
+            }
+            Phase::CaptureOnSecondTransition => {
+                self.sck_active();
+                self.sck_idle();
+                Some(true) == self.sda.is_high().ok()
+            }
+        }
+    }
+
+    /// Clock one edge pair while driving `bit` onto sda, honoring [`Phase`].
+    fn clock_drive(&mut self, bit: bool) {
+        match self.mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                self.sda.set_state(bit.into()).ok();
+                self.sck_active();
+                self.sck_idle();
+            }
+            Phase::CaptureOnSecondTransition => {
+                self.sck_active();
+                self.sda.set_state(bit.into()).ok();
+                self.sck_idle();
+            }
+        }
+    }
+
+    /// Release sda (drive it high, relying on the pull-up) so the slave can drive it.
+    fn release(&mut self) {
+        self.sda.set_high().ok();
+    }
+
+    /// Drive `bit` onto sda, then release the line and sample whatever the
+    /// slave drives back, honoring [`Phase`]. This is what makes a half-duplex
+    /// `transfer` possible on a single shared data pin.
+    ///
+    /// Unlike [`Self::clock_drive`] and [`Self::clock_sample`], this needs a
+    /// half-period delay on *both* sides of `release`: one to hold our driven
+    /// bit on the line through its edge before letting go, and another to let
+    /// the line settle into whatever the slave drives before we sample it.
+    /// That makes this three half-periods per bit rather than two.
+    fn clock_bit(&mut self, bit: bool) -> bool {
+        match self.mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                self.sda.set_state(bit.into()).ok();
+                self.sck_active();
+                self.release();
+                self.delay.delay_ns(self.half_period_ns);
+                let sampled = Some(true) == self.sda.is_high().ok();
+                self.sck_idle();
+                sampled
+            }
+            Phase::CaptureOnSecondTransition => {
+                self.sck_active();
+                self.sda.set_state(bit.into()).ok();
+                self.delay.delay_ns(self.half_period_ns);
+                self.release();
+                self.sck_idle();
+                Some(true) == self.sda.is_high().ok()
+            }
+        }
+    }
 }
-impl<Out, InOut> spi::ErrorType for SoftSpi<Out, InOut>
+impl<Out, InOut, Delay> spi::ErrorType for SoftSpi<Out, InOut, Delay>
 where
     Out: OutputPin,
     InOut: InputPin + OutputPin,
+    Delay: DelayNs,
 {
     type Error = spi::ErrorKind;
 }
 
-impl<Out, InOut> spi::SpiBus for SoftSpi<Out, InOut>
+impl<Out, InOut, Delay> spi::SpiBus for SoftSpi<Out, InOut, Delay>
 where
     Out: OutputPin,
     InOut: InputPin + OutputPin,
+    Delay: DelayNs,
 {
     /// Read `words` from the slave.
     ///
@@ -42,18 +212,14 @@ where
     /// Implementations are allowed to return before the operation is
     /// complete. See the [module-level documentation](self) for details.
     fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
-        self.sda.set_high().ok();
+        self.release();
         for bytes in 0..words.len() {
-            for _ in 0..8 {
-                // Clock high then low
-                self.sck.set_low().ok();
-                self.sck.set_high().ok();
-                // Read the MISO pin after clocking the data in
-                words[bytes] <<= 1;
-                if Some(true) == self.sda.is_high().ok() {
-                    words[bytes] += 1;
-                }
+            let mut byte = 0_u8;
+            for bits in 0..8 {
+                let sampled = self.clock_sample();
+                self.bit_in(&mut byte, bits, sampled);
             }
+            words[bytes] = byte;
         }
         Ok(())
     }
@@ -65,12 +231,8 @@ where
     fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
         for bytes in 0..words.len() {
             for bits in 0..8 {
-                let bit = (words[bytes] << bits) & 0x80;
-                //self.sda.set_state((bit != 0).into()).ok(); // Set the MOSI pin to the current bit value
-                self.sda.set_state((bit != 0).into()).ok();
-                // Clock high then low
-                self.sck.set_low().ok();
-                self.sck.set_high().ok();
+                let bit = self.bit_out(words[bytes], bits);
+                self.clock_drive(bit);
             }
         }
         Ok(())
@@ -87,7 +249,19 @@ where
     ///
     /// Implementations are allowed to return before the operation is
     /// complete. See the [module-level documentation](self) for details.
-    fn transfer(&mut self, _: &mut [u8], _: &[u8]) -> Result<(), Self::Error> {
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        for i in 0..read.len().max(write.len()) {
+            let out_byte = write.get(i).copied().unwrap_or(self.fill_byte);
+            let mut in_byte = 0_u8;
+            for bits in 0..8 {
+                let out_bit = self.bit_out(out_byte, bits);
+                let sampled = self.clock_bit(out_bit);
+                self.bit_in(&mut in_byte, bits, sampled);
+            }
+            if let Some(slot) = read.get_mut(i) {
+                *slot = in_byte;
+            }
+        }
         Ok(())
     }
 
@@ -97,7 +271,17 @@ where
     ///
     /// Implementations are allowed to return before the operation is
     /// complete. See the [module-level documentation](self) for details.
-    fn transfer_in_place(&mut self, _: &mut [u8]) -> Result<(), Self::Error> {
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for byte in words.iter_mut() {
+            let out_byte = *byte;
+            let mut in_byte = 0_u8;
+            for bits in 0..8 {
+                let out_bit = self.bit_out(out_byte, bits);
+                let sampled = self.clock_bit(out_bit);
+                self.bit_in(&mut in_byte, bits, sampled);
+            }
+            *byte = in_byte;
+        }
         Ok(())
     }
 
@@ -108,3 +292,238 @@ where
         Ok(())
     }
 }
+
+#[cfg(feature = "async")]
+impl<Out, InOut, Delay> SoftSpi<Out, InOut, Delay>
+where
+    Out: OutputPin,
+    InOut: InputPin + OutputPin,
+    Delay: DelayNs + embedded_hal_async::delay::DelayNs,
+{
+    /// Async counterpart of [`Self::sck_idle`], yielding to the executor for the half period.
+    async fn sck_idle_async(&mut self) {
+        match self.mode.polarity {
+            Polarity::IdleLow => self.sck.set_low().ok(),
+            Polarity::IdleHigh => self.sck.set_high().ok(),
+        };
+        embedded_hal_async::delay::DelayNs::delay_ns(&mut self.delay, self.half_period_ns).await;
+    }
+
+    /// Async counterpart of [`Self::sck_active`], yielding to the executor for the half period.
+    async fn sck_active_async(&mut self) {
+        match self.mode.polarity {
+            Polarity::IdleLow => self.sck.set_high().ok(),
+            Polarity::IdleHigh => self.sck.set_low().ok(),
+        };
+        embedded_hal_async::delay::DelayNs::delay_ns(&mut self.delay, self.half_period_ns).await;
+    }
+
+    /// Async counterpart of [`Self::clock_sample`].
+    async fn clock_sample_async(&mut self) -> bool {
+        match self.mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                self.sck_active_async().await;
+                let sampled = Some(true) == self.sda.is_high().ok();
+                self.sck_idle_async().await;
+                sampled
+            }
+            Phase::CaptureOnSecondTransition => {
+                self.sck_active_async().await;
+                self.sck_idle_async().await;
+                Some(true) == self.sda.is_high().ok()
+            }
+        }
+    }
+
+    /// Async counterpart of [`Self::clock_drive`].
+    async fn clock_drive_async(&mut self, bit: bool) {
+        match self.mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                self.sda.set_state(bit.into()).ok();
+                self.sck_active_async().await;
+                self.sck_idle_async().await;
+            }
+            Phase::CaptureOnSecondTransition => {
+                self.sck_active_async().await;
+                self.sda.set_state(bit.into()).ok();
+                self.sck_idle_async().await;
+            }
+        }
+    }
+
+    /// Async counterpart of [`Self::clock_bit`].
+    async fn clock_bit_async(&mut self, bit: bool) -> bool {
+        match self.mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                self.sda.set_state(bit.into()).ok();
+                self.sck_active_async().await;
+                self.release();
+                embedded_hal_async::delay::DelayNs::delay_ns(&mut self.delay, self.half_period_ns)
+                    .await;
+                let sampled = Some(true) == self.sda.is_high().ok();
+                self.sck_idle_async().await;
+                sampled
+            }
+            Phase::CaptureOnSecondTransition => {
+                self.sck_active_async().await;
+                self.sda.set_state(bit.into()).ok();
+                embedded_hal_async::delay::DelayNs::delay_ns(&mut self.delay, self.half_period_ns)
+                    .await;
+                self.release();
+                self.sck_idle_async().await;
+                Some(true) == self.sda.is_high().ok()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<Out, InOut, Delay> embedded_hal_async::spi::SpiBus for SoftSpi<Out, InOut, Delay>
+where
+    Out: OutputPin,
+    InOut: InputPin + OutputPin,
+    Delay: DelayNs + embedded_hal_async::delay::DelayNs,
+{
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.release();
+        for bytes in 0..words.len() {
+            let mut byte = 0_u8;
+            for bits in 0..8 {
+                let sampled = self.clock_sample_async().await;
+                self.bit_in(&mut byte, bits, sampled);
+            }
+            words[bytes] = byte;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        for bytes in 0..words.len() {
+            for bits in 0..8 {
+                let bit = self.bit_out(words[bytes], bits);
+                self.clock_drive_async(bit).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        for i in 0..read.len().max(write.len()) {
+            let out_byte = write.get(i).copied().unwrap_or(self.fill_byte);
+            let mut in_byte = 0_u8;
+            for bits in 0..8 {
+                let out_bit = self.bit_out(out_byte, bits);
+                let sampled = self.clock_bit_async(out_bit).await;
+                self.bit_in(&mut in_byte, bits, sampled);
+            }
+            if let Some(slot) = read.get_mut(i) {
+                *slot = in_byte;
+            }
+        }
+        Ok(())
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for byte in words.iter_mut() {
+            let out_byte = *byte;
+            let mut in_byte = 0_u8;
+            for bits in 0..8 {
+                let out_bit = self.bit_out(out_byte, bits);
+                let sampled = self.clock_bit_async(out_bit).await;
+                self.bit_in(&mut in_byte, bits, sampled);
+            }
+            *byte = in_byte;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use embedded_hal::spi::SpiBus;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State as PinState, Transaction as PinTransaction};
+    use std::{vec, vec::Vec};
+
+    fn sck_transactions(bits: usize) -> Vec<PinTransaction> {
+        // `new` first drives SCK to its idle (low) level.
+        core::iter::once(PinTransaction::set(PinState::Low))
+            .chain((0..bits).flat_map(|_| {
+                [PinTransaction::set(PinState::High), PinTransaction::set(PinState::Low)]
+            }))
+            .collect()
+    }
+
+    /// Run a single `transfer` and return the filled `read` buffer, driving
+    /// `sampled_bits` (MSB first, one entry per clocked bit) as the slave's
+    /// response on sda.
+    fn run_transfer(phase: Phase, write: &[u8], read_len: usize, sampled_bits: &[bool]) -> Vec<u8> {
+        let bits = sampled_bits.len();
+        let sck = PinMock::new(&sck_transactions(bits));
+
+        let mut sda_transactions = Vec::new();
+        for i in 0..bits / 8 {
+            let byte = write.get(i).copied().unwrap_or(0x00);
+            for b in 0..8 {
+                let out_bit = (byte << b) & 0x80 != 0;
+                sda_transactions.push(PinTransaction::set(if out_bit {
+                    PinState::High
+                } else {
+                    PinState::Low
+                }));
+                sda_transactions.push(PinTransaction::set(PinState::High)); // release, held through the settle delay
+                sda_transactions.push(PinTransaction::get(if sampled_bits[i * 8 + b] {
+                    PinState::High
+                } else {
+                    PinState::Low
+                }));
+            }
+        }
+        let sda = PinMock::new(&sda_transactions);
+
+        let mode = Mode {
+            polarity: Polarity::IdleLow,
+            phase,
+        };
+        let mut spi = SoftSpi::new(sck, sda, mode, BitOrder::MsbFirst, NoopDelay::new(), 0);
+
+        let mut read = vec![0u8; read_len];
+        spi.transfer(&mut read, write).unwrap();
+
+        spi.sck.done();
+        spi.sda.done();
+        read
+    }
+
+    #[test]
+    fn transfer_first_transition_write_longer_discards_incoming() {
+        let read = run_transfer(Phase::CaptureOnFirstTransition, &[0xB2], 0, &[true; 8]);
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn transfer_first_transition_read_longer_uses_fill_byte() {
+        let sampled = [true, false, true, false, true, false, true, false];
+        let read = run_transfer(Phase::CaptureOnFirstTransition, &[], 1, &sampled);
+        assert_eq!(read, [0xAA]);
+    }
+
+    #[test]
+    fn transfer_second_transition_write_longer_discards_incoming() {
+        let read = run_transfer(Phase::CaptureOnSecondTransition, &[0xB2], 0, &[true; 8]);
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn transfer_second_transition_read_longer_uses_fill_byte() {
+        let sampled = [true, false, true, false, true, false, true, false];
+        let read = run_transfer(Phase::CaptureOnSecondTransition, &[], 1, &sampled);
+        assert_eq!(read, [0xAA]);
+    }
+}
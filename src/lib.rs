@@ -0,0 +1,34 @@
+//! Bit-banged (software) SPI master implementations of
+//! [`embedded_hal::spi::SpiBus`], driven entirely through GPIO pins.
+//!
+//! Two variants are provided:
+//!
+//! - [`full_duplex::SoftSpi`] — the classic 4-wire bus with separate MOSI
+//!   and MISO pins.
+//! - [`half_duplex::SoftSpi`] — a 3-wire bus that shares a single
+//!   bidirectional data pin for both MOSI and MISO.
+#![no_std]
+
+pub mod full_duplex;
+pub mod half_duplex;
+pub mod spi_device;
+
+/// Bit order used when shifting words onto and off of the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Most significant bit first. This is the default for most SPI peripherals.
+    MsbFirst,
+    /// Least significant bit first.
+    LsbFirst,
+}
+
+/// Convert a target SCK frequency into the half-period (in nanoseconds) the
+/// `SoftSpi` variants delay for after every clock edge.
+///
+/// Written as `500_000_000 / frequency_hz` (rather than `1e9 / (2 * frequency_hz)`)
+/// so it can't overflow. `frequency_hz == 0` trips a `debug_assert!` in debug
+/// builds; in release builds it saturates to `u32::MAX` instead of dividing by zero.
+pub(crate) fn half_period_ns_from_frequency(frequency_hz: u32) -> u32 {
+    debug_assert!(frequency_hz > 0, "frequency_hz must be non-zero");
+    500_000_000_u32.checked_div(frequency_hz).unwrap_or(u32::MAX)
+}
@@ -0,0 +1,72 @@
+use embedded_hal::{
+    delay::DelayNs,
+    digital::OutputPin,
+    spi::{self, ErrorType, Operation, SpiBus, SpiDevice},
+};
+
+/// Wraps a [`SpiBus`] together with a chip-select pin and a delay source,
+/// implementing [`SpiDevice`] so the software bus can be dropped into drivers
+/// written against the standard `embedded-hal` device abstraction.
+///
+/// The device owns `bus` for its whole lifetime and drives a single
+/// chip-select; it does not arbitrate one bus between several chip-selects.
+pub struct SoftSpiDevice<BUS, CS: OutputPin, Delay: DelayNs> {
+    bus: BUS,
+    cs: CS,
+    delay: Delay,
+}
+
+impl<BUS, CS: OutputPin, Delay: DelayNs> SoftSpiDevice<BUS, CS, Delay> {
+    pub fn new(bus: BUS, cs: CS, delay: Delay) -> Self {
+        Self { bus, cs, delay }
+    }
+}
+
+/// Error raised by a [`SoftSpiDevice`], from either the underlying bus or the chip-select pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<BusError, PinError> {
+    /// The underlying [`SpiBus`] operation failed.
+    Spi(BusError),
+    /// Driving the chip-select pin failed.
+    Cs(PinError),
+}
+
+impl<BusError: spi::Error, PinError: core::fmt::Debug> spi::Error for Error<BusError, PinError> {
+    fn kind(&self) -> spi::ErrorKind {
+        match self {
+            Error::Spi(e) => e.kind(),
+            Error::Cs(_) => spi::ErrorKind::Other,
+        }
+    }
+}
+
+impl<BUS: ErrorType, CS: OutputPin, Delay: DelayNs> ErrorType for SoftSpiDevice<BUS, CS, Delay> {
+    type Error = Error<BUS::Error, CS::Error>;
+}
+
+impl<BUS: SpiBus, CS: OutputPin, Delay: DelayNs> SpiDevice for SoftSpiDevice<BUS, CS, Delay> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        self.cs.set_low().map_err(Error::Cs)?;
+
+        let op_res = operations.iter_mut().try_for_each(|op| match op {
+            Operation::Read(words) => self.bus.read(words),
+            Operation::Write(words) => self.bus.write(words),
+            Operation::Transfer(read, write) => self.bus.transfer(read, write),
+            Operation::TransferInPlace(words) => self.bus.transfer_in_place(words),
+            Operation::DelayNs(ns) => {
+                let _ = self.bus.flush();
+                self.delay.delay_ns(*ns);
+                Ok(())
+            }
+        });
+
+        let flush_res = self.bus.flush();
+        let cs_res = self.cs.set_high();
+
+        op_res.map_err(Error::Spi)?;
+        flush_res.map_err(Error::Spi)?;
+        cs_res.map_err(Error::Cs)?;
+
+        Ok(())
+    }
+}